@@ -0,0 +1,85 @@
+//! A BK-tree over 64-bit perceptual hashes, keyed by Hamming distance.
+//!
+//! Each node's children are indexed by the distance from the node's own hash,
+//! which lets a radius query prune to only the children whose edge falls
+//! within `[distance - radius, distance + radius]` (the triangle inequality
+//! rules out everything else).
+
+use std::collections::HashMap;
+
+pub fn hamming(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+struct Node<T> {
+    hash: u64,
+    item: T,
+    children: HashMap<u32, Box<Node<T>>>,
+}
+
+impl<T> Node<T> {
+    fn insert(&mut self, hash: u64, item: T) {
+        let distance = hamming(self.hash, hash);
+        match self.children.get_mut(&distance) {
+            Some(child) => child.insert(hash, item),
+            None => {
+                self.children.insert(
+                    distance,
+                    Box::new(Node {
+                        hash,
+                        item,
+                        children: HashMap::new(),
+                    }),
+                );
+            }
+        }
+    }
+
+    fn find_within<'a>(&'a self, hash: u64, radius: u32, matches: &mut Vec<&'a T>) {
+        let distance = hamming(self.hash, hash);
+        if distance <= radius {
+            matches.push(&self.item);
+        }
+
+        let lo = distance.saturating_sub(radius);
+        let hi = distance.saturating_add(radius);
+        for edge in lo..=hi {
+            if let Some(child) = self.children.get(&edge) {
+                child.find_within(hash, radius, matches);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct BkTree<T> {
+    root: Option<Box<Node<T>>>,
+}
+
+impl<T> BkTree<T> {
+    pub fn new() -> Self {
+        BkTree { root: None }
+    }
+
+    pub fn insert(&mut self, hash: u64, item: T) {
+        match &mut self.root {
+            Some(root) => root.insert(hash, item),
+            None => {
+                self.root = Some(Box::new(Node {
+                    hash,
+                    item,
+                    children: HashMap::new(),
+                }))
+            }
+        }
+    }
+
+    /// Returns every inserted item whose hash is within `radius` of `hash`.
+    pub fn find_within(&self, hash: u64, radius: u32) -> Vec<&T> {
+        let mut matches = Vec::new();
+        if let Some(root) = &self.root {
+            root.find_within(hash, radius, &mut matches);
+        }
+        matches
+    }
+}
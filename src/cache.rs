@@ -0,0 +1,109 @@
+//! A central cache keyed by canonical path, mtime, size and component counts,
+//! so that moving or re-timestamping an image is detected instead of only
+//! checking whether a sidecar `.bh` file happens to exist.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+struct CacheEntry {
+    mtime: u64,
+    size: u64,
+    components_x: usize,
+    components_y: usize,
+    blurhash: String,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Cache {
+    entries: HashMap<String, CacheEntry>,
+}
+
+impl Cache {
+    /// Loads the cache from `path`, or starts empty if it doesn't exist yet
+    /// or fails to parse.
+    pub async fn load(path: &Path) -> Self {
+        match tokio::fs::read(path).await {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_default(),
+            Err(_) => Cache::default(),
+        }
+    }
+
+    /// Returns the cached BlurHash if `path`'s mtime, size and component
+    /// counts still match what was stored.
+    pub fn lookup(
+        &self,
+        path: &Path,
+        mtime: u64,
+        size: u64,
+        components_x: usize,
+        components_y: usize,
+    ) -> Option<&str> {
+        let entry = self.entries.get(&key_for(path))?;
+        if entry.mtime == mtime
+            && entry.size == size
+            && entry.components_x == components_x
+            && entry.components_y == components_y
+        {
+            Some(entry.blurhash.as_str())
+        } else {
+            None
+        }
+    }
+
+    pub fn insert(
+        &mut self,
+        path: &Path,
+        mtime: u64,
+        size: u64,
+        components_x: usize,
+        components_y: usize,
+        blurhash: String,
+    ) {
+        self.entries.insert(
+            key_for(path),
+            CacheEntry {
+                mtime,
+                size,
+                components_x,
+                components_y,
+                blurhash,
+            },
+        );
+    }
+
+    /// Serializes the cache to `path`, writing to a temp file first and
+    /// renaming it into place so a crash mid-write can't corrupt the cache.
+    pub async fn save_atomic(&self, path: &Path) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let json = serde_json::to_vec_pretty(self)?;
+        let tmp_path = path.with_extension("tmp");
+        tokio::fs::write(&tmp_path, json).await?;
+        tokio::fs::rename(&tmp_path, path).await?;
+        Ok(())
+    }
+}
+
+fn key_for(path: &Path) -> String {
+    std::fs::canonicalize(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// File stat used to key cache entries: seconds-resolution mtime and byte size.
+pub fn stat(path: &Path) -> std::io::Result<(u64, u64)> {
+    let metadata = std::fs::metadata(path)?;
+    let mtime = metadata
+        .modified()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    Ok((mtime, metadata.len()))
+}
+
+pub type SharedCache = std::sync::Arc<tokio::sync::Mutex<Cache>>;
+
+pub fn shared(cache: Cache) -> SharedCache {
+    std::sync::Arc::new(tokio::sync::Mutex::new(cache))
+}
@@ -0,0 +1,38 @@
+//! HEIC/HEIF decoding, backed by libheif via the `heif` cargo feature since it
+//! requires the system `libheif` library to be installed.
+
+use std::path::Path;
+use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+/// Extensions recognized as HEIC/HEIF files.
+pub fn extensions() -> &'static [&'static str] {
+    &["heic", "heif"]
+}
+
+pub fn is_heic_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| extensions().contains(&ext.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Decodes a HEIC/HEIF file into an RGBA8 buffer along with its dimensions.
+pub fn decode(path: &Path) -> Result<(usize, usize, Vec<u8>), Box<dyn std::error::Error + Send + Sync>> {
+    let lib_heif = LibHeif::new();
+    let ctx = HeifContext::read_from_file(path.to_str().ok_or("non UTF-8 path")?)?;
+    let handle = ctx.primary_image_handle()?;
+    let image = lib_heif.decode(&handle, ColorSpace::Rgb(RgbChroma::Rgba), None)?;
+
+    let plane = image.planes().interleaved.ok_or("missing interleaved RGBA plane")?;
+    let width = plane.width as usize;
+    let height = plane.height as usize;
+    let stride = plane.stride;
+
+    let mut rgba = Vec::with_capacity(width * height * 4);
+    for row in 0..height {
+        let start = row * stride;
+        rgba.extend_from_slice(&plane.data[start..start + width * 4]);
+    }
+
+    Ok((width, height, rgba))
+}
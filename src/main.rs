@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use image::GenericImageView;
 use std::path::{Path, PathBuf};
 use std::fs;
@@ -7,6 +7,17 @@ use futures::future::join_all;
 use std::sync::Arc;
 
 mod blurhash;
+mod raw;
+#[cfg(feature = "heif")]
+mod heic;
+mod video;
+mod bktree;
+mod phash;
+mod similarity;
+mod cache;
+mod output;
+
+use output::Output;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -22,52 +33,178 @@ struct Args {
     /// Number of Y components for BlurHash
     #[arg(short = 'y', long, default_value_t = 3)]
     components_y: usize,
+
+    /// For video inputs, pick the thumbnail frame at this percentage of the
+    /// video's duration instead of the default fixed 1 second mark
+    #[arg(long)]
+    frame_at: Option<f64>,
+
+    /// Group perceptually similar images whose BlurHashes are within this
+    /// Hamming distance of each other, in addition to emitting results through
+    /// the selected `--output` backend
+    #[arg(long)]
+    find_similar: Option<u32>,
+
+    /// Cache computed BlurHashes in this file, keyed by path, mtime, size and
+    /// component counts, instead of relying on sidecar `.bh` files to decide
+    /// what to skip
+    #[arg(long)]
+    cache: Option<PathBuf>,
+
+    /// Where to send computed BlurHashes
+    #[arg(long, value_enum, default_value = "sidecar")]
+    output: OutputKind,
+
+    /// Manifest file path for `--output jsonl` (default: blurhash.jsonl)
+    #[arg(long)]
+    manifest: Option<PathBuf>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, ValueEnum)]
+enum OutputKind {
+    Sidecar,
+    Jsonl,
+    Stdout,
+}
+
+/// Result of attempting to turn a single input file into a BlurHash.
+#[derive(Debug)]
+enum ProcessOutcome {
+    Succeeded { hash: String },
+    /// `hash` carries the previously persisted hash when one is known, so
+    /// `--find-similar` can still cluster files that weren't recomputed.
+    Skipped { reason: String, hash: Option<String> },
+    Failed { reason: String },
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let args = Args::parse();
 
-    let image_paths = get_image_paths(&args.inputs)?;
+    let output: Arc<dyn Output> = match args.output {
+        OutputKind::Sidecar => Arc::new(output::SidecarOutput),
+        OutputKind::Stdout => Arc::new(output::StdoutOutput),
+        OutputKind::Jsonl => {
+            let manifest_path = args
+                .manifest
+                .clone()
+                .unwrap_or_else(|| PathBuf::from("blurhash.jsonl"));
+            Arc::new(output::JsonlOutput::open(&manifest_path).await?)
+        }
+    };
+
+    // Skip decisions happen inside `process_image` (via the cache or the
+    // output backend) rather than during the walk whenever something still
+    // needs to see every candidate file: the cache keys on mtime/size, and
+    // `--find-similar` needs a hash for every selected image, not only the
+    // ones recomputed this run.
+    let collect_all = args.cache.is_some() || args.find_similar.is_some();
+    let image_paths = get_image_paths(&args.inputs, collect_all, &output)?;
+
+    let shared_cache = match &args.cache {
+        Some(cache_path) => Some(cache::shared(cache::Cache::load(cache_path).await)),
+        None => None,
+    };
 
     // Create a semaphore to limit concurrent tasks
     let semaphore = Arc::new(Semaphore::new(num_cpus::get()));
 
+    // Third-party decoders are treated as untrusted: suppress panic backtraces
+    // for the duration of the batch so a crashing decoder doesn't spam the
+    // terminal, then restore the default hook once every file has been handled.
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(|_| {}));
+
     let tasks: Vec<_> = image_paths
         .into_iter()
         .map(|path| {
             let sem = semaphore.clone();
             let components_x = args.components_x;
             let components_y = args.components_y;
+            let frame_at = args.frame_at;
+            let cache = shared_cache.clone();
+            let output = output.clone();
             tokio::spawn(async move {
                 let _permit = sem.acquire().await.unwrap();
-                process_image(path, components_x, components_y).await
+                let outcome =
+                    process_image(path.clone(), components_x, components_y, frame_at, cache, output).await;
+                (path, outcome)
             })
         })
         .collect();
 
-    // Wait for all tasks to complete
     let results = join_all(tasks).await;
 
-    // Check for any errors
+    std::panic::set_hook(default_hook);
+
+    let mut succeeded = 0usize;
+    let mut skipped = 0usize;
+    let mut failures: Vec<(PathBuf, String)> = Vec::new();
+    let mut hashes: Vec<(PathBuf, String)> = Vec::new();
+
     for result in results {
-        if let Err(e) = result? {
-            eprintln!("Error processing image: {}", e);
+        let (path, outcome) = result?;
+        match outcome {
+            ProcessOutcome::Succeeded { hash } => {
+                hashes.push((path, hash));
+                succeeded += 1;
+            }
+            ProcessOutcome::Skipped { reason, hash } => {
+                println!("Skipping {}: {}", path.display(), reason);
+                if let Some(hash) = hash {
+                    hashes.push((path, hash));
+                }
+                skipped += 1;
+            }
+            ProcessOutcome::Failed { reason } => failures.push((path, reason)),
+        }
+    }
+
+    println!(
+        "\nDone: {} succeeded, {} skipped, {} failed",
+        succeeded,
+        skipped,
+        failures.len()
+    );
+
+    if !failures.is_empty() {
+        println!("Files that could not be processed:");
+        for (path, reason) in &failures {
+            println!("  {}: {}", path.display(), reason);
+        }
+    }
+
+    if let Some(radius) = args.find_similar {
+        let clusters = similarity::find_clusters(&hashes, radius)?;
+        println!("\nFound {} group(s) of similar images:", clusters.len());
+        for (i, group) in clusters.iter().enumerate() {
+            println!("Group {}:", i + 1);
+            for path in group {
+                println!("  {}", path.display());
+            }
         }
     }
 
+    if let (Some(cache_path), Some(cache)) = (&args.cache, &shared_cache) {
+        cache.lock().await.save_atomic(cache_path).await?;
+    }
+
     Ok(())
 }
 
-fn get_image_paths(inputs: &[PathBuf]) -> Result<Vec<PathBuf>, Box<dyn std::error::Error + Send + Sync>> {
+fn get_image_paths(
+    inputs: &[PathBuf],
+    collect_all: bool,
+    output: &Arc<dyn Output>,
+) -> Result<Vec<PathBuf>, Box<dyn std::error::Error + Send + Sync>> {
     let mut image_paths = Vec::new();
 
     for input in inputs {
         if input.as_os_str().is_empty() || input == Path::new(".") {
             // If input is empty or ".", use the current directory
-            search_directory(&std::env::current_dir()?, &mut image_paths)?;
+            search_directory(&std::env::current_dir()?, &mut image_paths, collect_all, output)?;
         } else if input.is_dir() {
-            search_directory(input, &mut image_paths)?;
+            search_directory(input, &mut image_paths, collect_all, output)?;
         } else if is_image_file(input) {
             image_paths.push(input.to_path_buf());
         }
@@ -78,61 +215,196 @@ fn get_image_paths(inputs: &[PathBuf]) -> Result<Vec<PathBuf>, Box<dyn std::erro
 
 fn is_image_file(path: &Path) -> bool {
     let extensions = ["jpg", "jpeg", "png", "gif", "bmp", "tiff"];
-    path.extension()
+    let recognized = path
+        .extension()
         .and_then(|ext| ext.to_str())
         .map(|ext| extensions.contains(&ext.to_lowercase().as_str()))
-        .unwrap_or(false)
+        .unwrap_or(false);
+
+    recognized || raw::is_raw_file(path) || is_heic_file(path) || video::is_video_file(path)
+}
+
+#[cfg(feature = "heif")]
+fn is_heic_file(path: &Path) -> bool {
+    heic::is_heic_file(path)
 }
 
-async fn process_image(input: PathBuf, components_x: usize, components_y: usize) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    // Generate the output filename
-    let mut output_filename = input.clone();
-    let new_extension = format!("{}.bh", output_filename.extension().unwrap_or_default().to_str().unwrap_or(""));
-    output_filename.set_extension(new_extension);
+#[cfg(not(feature = "heif"))]
+fn is_heic_file(_path: &Path) -> bool {
+    false
+}
 
-    // Check if the .bh file already exists
-    if output_filename.exists() {
-        println!("Skipping {}: BlurHash file already exists", input.display());
-        return Ok(());
+/// Decodes `input` and computes its BlurHash, returning its pixel dimensions
+/// alongside the hash so callers (e.g. the JSONL output backend) don't need
+/// to open the file a second time to learn them. Kept synchronous so it can
+/// be run inside `catch_unwind`: `image`'s decoders are third-party code and
+/// occasionally panic on malformed files instead of returning `Err`.
+fn decode_and_hash(
+    input: &Path,
+    components_x: usize,
+    components_y: usize,
+) -> Result<(usize, usize, String), Box<dyn std::error::Error + Send + Sync>> {
+    let (width, height, pixels) = if raw::is_raw_file(input) {
+        raw::decode(input)?
+    } else if is_heic_file(input) {
+        decode_heic(input)?
+    } else {
+        let img = image::open(input)?;
+        let (width, height) = img.dimensions();
+        (width as usize, height as usize, img.to_rgba8().into_raw())
+    };
+
+    let blurhash = blurhash::encode(pixels, components_x, components_y, width, height)?;
+
+    Ok((width, height, blurhash))
+}
+
+#[cfg(feature = "heif")]
+fn decode_heic(input: &Path) -> Result<(usize, usize, Vec<u8>), Box<dyn std::error::Error + Send + Sync>> {
+    heic::decode(input)
+}
+
+#[cfg(not(feature = "heif"))]
+fn decode_heic(_input: &Path) -> Result<(usize, usize, Vec<u8>), Box<dyn std::error::Error + Send + Sync>> {
+    Err("HEIC/HEIF support requires building with the `heif` feature".into())
+}
+
+fn panic_payload_message(payload: &(dyn std::any::Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        (*s).to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "decoder panicked".to_string()
     }
+}
 
-    let img = tokio::task::spawn_blocking(move || image::open(&input)).await??;
-    let (width, height) = img.dimensions();
-    let rgba_image = img.to_rgba8();
-    let pixels: Vec<u8> = rgba_image.into_raw();
+/// Runs `decode_and_hash` on a blocking thread under `catch_unwind`, so a
+/// panicking decoder becomes an error string instead of aborting the task.
+/// Used for still images, RAW, HEIC and (via an already-extracted frame file)
+/// video sources alike.
+async fn guarded_decode(
+    input: PathBuf,
+    components_x: usize,
+    components_y: usize,
+) -> Result<(usize, usize, String), String> {
+    let guarded = tokio::task::spawn_blocking(move || {
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            decode_and_hash(&input, components_x, components_y)
+        }))
+    })
+    .await;
 
-    let blurhash = blurhash::encode(
-        pixels,
-        components_x,
-        components_y,
-        width as usize,
-        height as usize,
-    )?;
+    match guarded {
+        Ok(Ok(Ok(result))) => Ok(result),
+        Ok(Ok(Err(e))) => Err(e.to_string()),
+        Ok(Err(panic)) => Err(panic_payload_message(&*panic)),
+        Err(join_err) => Err(join_err.to_string()),
+    }
+}
 
-    // Save the BlurHash to the file
-    tokio::fs::write(&output_filename, &blurhash).await?;
+async fn process_image(
+    input: PathBuf,
+    components_x: usize,
+    components_y: usize,
+    frame_at: Option<f64>,
+    cache: Option<cache::SharedCache>,
+    output: Arc<dyn Output>,
+) -> ProcessOutcome {
+    let stat = cache.is_some().then(|| cache::stat(&input)).and_then(Result::ok);
 
-    println!("BlurHash saved to: {}", output_filename.display());
+    if let (Some(shared), Some((mtime, size))) = (&cache, stat) {
+        let cached_hash = shared
+            .lock()
+            .await
+            .lookup(&input, mtime, size, components_x, components_y)
+            .map(str::to_string);
+        if let Some(hash) = cached_hash {
+            return ProcessOutcome::Skipped {
+                reason: "up to date in cache".to_string(),
+                hash: Some(hash),
+            };
+        }
+    } else if cache.is_none() {
+        if let Some(hash) = output.read_existing(&input) {
+            return ProcessOutcome::Skipped {
+                reason: "already up to date".to_string(),
+                hash: Some(hash),
+            };
+        }
+    }
 
-    Ok(())
+    // Video frames are extracted to a temp file first, then decoded through
+    // the exact same guarded path as every other image source.
+    let is_video = video::is_video_file(&input);
+    let (decode_path, temp_frame) = if is_video {
+        match video::extract_frame_to_file(&input, frame_at).await {
+            Ok(frame_path) => (frame_path.clone(), Some(frame_path)),
+            Err(e) => {
+                return ProcessOutcome::Failed {
+                    reason: e.to_string(),
+                }
+            }
+        }
+    } else {
+        (input.clone(), None)
+    };
+
+    let decode_result = guarded_decode(decode_path, components_x, components_y).await;
+
+    if let Some(frame_path) = &temp_frame {
+        let _ = tokio::fs::remove_file(frame_path).await;
+    }
+
+    let (width, height, blurhash) = match decode_result {
+        Ok(result) => result,
+        Err(reason) => return ProcessOutcome::Failed { reason },
+    };
+
+    if let Err(e) = output
+        .emit(&input, (width as u32, height as u32), (components_x, components_y), &blurhash)
+        .await
+    {
+        return ProcessOutcome::Failed {
+            reason: e.to_string(),
+        };
+    }
+
+    if let (Some(shared), Some((mtime, size))) = (&cache, stat) {
+        shared
+            .lock()
+            .await
+            .insert(&input, mtime, size, components_x, components_y, blurhash.clone());
+    }
+
+    ProcessOutcome::Succeeded { hash: blurhash }
 }
 
-fn search_directory(dir: &Path, image_paths: &mut Vec<PathBuf>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+fn search_directory(
+    dir: &Path,
+    image_paths: &mut Vec<PathBuf>,
+    collect_all: bool,
+    output: &Arc<dyn Output>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     for entry in fs::read_dir(dir)? {
         let entry = entry?;
         let path = entry.path();
         if path.is_dir() {
-            search_directory(&path, image_paths)?;
+            search_directory(&path, image_paths, collect_all, output)?;
         } else if is_image_file(&path) {
-            // Check if a corresponding .bh file already exists
-            let mut bh_path = path.clone();
-            let new_extension = format!("{}.bh", bh_path.extension().unwrap_or_default().to_str().unwrap_or(""));
-            bh_path.set_extension(new_extension);
-            
-            if !bh_path.exists() {
+            // With a central cache, staleness is decided per-file from mtime/size
+            // rather than from the output backend's notion of "up to date"; and
+            // `--find-similar` needs every selected file's hash, recomputed or
+            // not. Either way, defer the skip decision to `process_image`.
+            if collect_all {
+                image_paths.push(path);
+                continue;
+            }
+
+            if !output.is_up_to_date(&path) {
                 image_paths.push(path);
             } else {
-                println!("Skipping {}: BlurHash file already exists", path.display());
+                println!("Skipping {}: already up to date", path.display());
             }
         }
     }
@@ -0,0 +1,149 @@
+//! Pluggable destinations for a computed BlurHash, so the encoding pipeline
+//! doesn't have to hard-code writing a sidecar file next to every source image.
+
+use async_trait::async_trait;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+
+#[async_trait]
+pub trait Output: Send + Sync {
+    /// Whether `source` already has a persisted result and processing it
+    /// again can be skipped. Backends with no cheap way to tell this should
+    /// just return `false` and rely on `--cache` instead.
+    fn is_up_to_date(&self, _source: &Path) -> bool {
+        false
+    }
+
+    /// Returns the previously persisted hash for `source`, if any, so callers
+    /// that skip re-encoding (e.g. for `--find-similar`) can still use it.
+    fn read_existing(&self, _source: &Path) -> Option<String> {
+        None
+    }
+
+    async fn emit(
+        &self,
+        source: &Path,
+        dimensions: (u32, u32),
+        components: (usize, usize),
+        hash: &str,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>>;
+}
+
+/// Writes a `<name>.<ext>.bh` sidecar next to each source image. The original
+/// default behavior, kept so existing workflows are unaffected.
+pub struct SidecarOutput;
+
+impl SidecarOutput {
+    pub fn sidecar_path(source: &Path) -> PathBuf {
+        let mut path = source.to_path_buf();
+        let new_extension = format!("{}.bh", path.extension().unwrap_or_default().to_str().unwrap_or(""));
+        path.set_extension(new_extension);
+        path
+    }
+}
+
+#[async_trait]
+impl Output for SidecarOutput {
+    fn is_up_to_date(&self, source: &Path) -> bool {
+        Self::sidecar_path(source).exists()
+    }
+
+    fn read_existing(&self, source: &Path) -> Option<String> {
+        std::fs::read_to_string(Self::sidecar_path(source)).ok()
+    }
+
+    async fn emit(
+        &self,
+        source: &Path,
+        _dimensions: (u32, u32),
+        _components: (usize, usize),
+        hash: &str,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let path = Self::sidecar_path(source);
+        tokio::fs::write(&path, hash).await?;
+        println!("BlurHash saved to: {}", path.display());
+        Ok(())
+    }
+}
+
+/// Prints each result to stdout as it's computed.
+pub struct StdoutOutput;
+
+#[async_trait]
+impl Output for StdoutOutput {
+    async fn emit(
+        &self,
+        source: &Path,
+        dimensions: (u32, u32),
+        components: (usize, usize),
+        hash: &str,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        println!(
+            "{}\t{}x{}\t{}x{}\t{}",
+            source.display(),
+            dimensions.0,
+            dimensions.1,
+            components.0,
+            components.1,
+            hash
+        );
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct ManifestRecord<'a> {
+    path: &'a str,
+    width: u32,
+    height: u32,
+    x: usize,
+    y: usize,
+    hash: &'a str,
+}
+
+/// Appends one JSON object per result to a single manifest file, so results
+/// can feed a database or build step without littering the source tree.
+pub struct JsonlOutput {
+    file: Mutex<tokio::fs::File>,
+}
+
+impl JsonlOutput {
+    pub async fn open(path: &Path) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .await?;
+        Ok(JsonlOutput {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+#[async_trait]
+impl Output for JsonlOutput {
+    async fn emit(
+        &self,
+        source: &Path,
+        dimensions: (u32, u32),
+        components: (usize, usize),
+        hash: &str,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let record = ManifestRecord {
+            path: &source.to_string_lossy(),
+            width: dimensions.0,
+            height: dimensions.1,
+            x: components.0,
+            y: components.1,
+            hash,
+        };
+        let mut line = serde_json::to_string(&record)?;
+        line.push('\n');
+
+        let mut file = self.file.lock().await;
+        file.write_all(line.as_bytes()).await?;
+        Ok(())
+    }
+}
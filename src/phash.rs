@@ -0,0 +1,128 @@
+//! Derives a cheap 64-bit perceptual hash from an already-computed BlurHash,
+//! so near-duplicate detection doesn't need to touch the original pixels again.
+//!
+//! This decodes the BlurHash string itself (its own base83 + DCT coefficients)
+//! rather than going through `blurhash::decode`, since that module's only
+//! established contract is `encode`.
+
+const BASE83_ALPHABET: &[u8] =
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz#$%*+,-.:;=?@[]^_{|}~";
+
+fn decode83(s: &str) -> Result<i32, Box<dyn std::error::Error + Send + Sync>> {
+    let mut value = 0i32;
+    for c in s.bytes() {
+        let digit = BASE83_ALPHABET
+            .iter()
+            .position(|&b| b == c)
+            .ok_or("invalid base83 character in BlurHash")?;
+        value = value * 83 + digit as i32;
+    }
+    Ok(value)
+}
+
+fn sign(n: f32) -> f32 {
+    if n < 0.0 {
+        -1.0
+    } else {
+        1.0
+    }
+}
+
+fn decode_dc(value: i32) -> (f32, f32, f32) {
+    (
+        ((value >> 16) & 255) as f32 / 255.0,
+        ((value >> 8) & 255) as f32 / 255.0,
+        (value & 255) as f32 / 255.0,
+    )
+}
+
+fn decode_ac(value: i32, maximum_value: f32) -> (f32, f32, f32) {
+    let r = (value / (19 * 19)) as f32 - 9.0;
+    let g = ((value / 19) % 19) as f32 - 9.0;
+    let b = (value % 19) as f32 - 9.0;
+    (
+        sign(r) * (r.abs() * r.abs()) / 81.0 * maximum_value,
+        sign(g) * (g.abs() * g.abs()) / 81.0 * maximum_value,
+        sign(b) * (b.abs() * b.abs()) / 81.0 * maximum_value,
+    )
+}
+
+/// Decodes `hash`'s DCT components to a `width`x`height` grid of approximate
+/// luminance values, without bothering to reconstruct full RGB (the dhash
+/// below only ever compares brightness of neighboring cells).
+fn decode_luminance_grid(
+    hash: &str,
+    width: usize,
+    height: usize,
+) -> Result<Vec<f32>, Box<dyn std::error::Error + Send + Sync>> {
+    if hash.len() < 6 {
+        return Err("BlurHash string too short".into());
+    }
+
+    let size_flag = decode83(&hash[0..1])?;
+    let num_x = (size_flag % 9) as usize + 1;
+    let num_y = (size_flag / 9) as usize + 1;
+
+    let quantized_max = decode83(&hash[1..2])?;
+    let maximum_value = (quantized_max + 1) as f32 / 166.0;
+
+    let expected_len = 4 + 2 * num_x * num_y;
+    if hash.len() != expected_len {
+        return Err("BlurHash component count doesn't match its length".into());
+    }
+
+    let mut components = Vec::with_capacity(num_x * num_y);
+    components.push(decode_dc(decode83(&hash[2..6])?));
+    for i in 1..num_x * num_y {
+        let start = 4 + i * 2;
+        let value = decode83(&hash[start..start + 2])?;
+        components.push(decode_ac(value, maximum_value));
+    }
+
+    let mut grid = vec![0f32; width * height];
+    for y in 0..height {
+        for x in 0..width {
+            let mut r = 0f32;
+            let mut g = 0f32;
+            let mut b = 0f32;
+            for j in 0..num_y {
+                for i in 0..num_x {
+                    let basis = (std::f32::consts::PI * x as f32 * i as f32 / width as f32).cos()
+                        * (std::f32::consts::PI * y as f32 * j as f32 / height as f32).cos();
+                    let (cr, cg, cb) = components[j * num_x + i];
+                    r += cr * basis;
+                    g += cg * basis;
+                    b += cb * basis;
+                }
+            }
+            grid[y * width + x] = 0.299 * r + 0.587 * g + 0.114 * b;
+        }
+    }
+
+    Ok(grid)
+}
+
+/// Decodes `hash`'s low-frequency components down to a small grayscale grid
+/// and builds a difference-hash: one bit per horizontally adjacent pair of
+/// cells, set when the left cell is brighter than the right one.
+pub fn dhash_from_blurhash(hash: &str) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+    const GRID_WIDTH: usize = 9;
+    const GRID_HEIGHT: usize = 8;
+
+    let grid = decode_luminance_grid(hash, GRID_WIDTH, GRID_HEIGHT)?;
+
+    let mut bits: u64 = 0;
+    let mut bit_index = 0;
+    for y in 0..GRID_HEIGHT {
+        for x in 0..GRID_WIDTH - 1 {
+            let left = grid[y * GRID_WIDTH + x];
+            let right = grid[y * GRID_WIDTH + x + 1];
+            if left > right {
+                bits |= 1 << bit_index;
+            }
+            bit_index += 1;
+        }
+    }
+
+    Ok(bits)
+}
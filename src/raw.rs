@@ -0,0 +1,82 @@
+//! Minimal RAW decoding support for camera formats (NEF, CR2, DNG, ARW, RW2, RAF, ...).
+//!
+//! We only need a reasonable RGBA preview to feed into `blurhash::encode`, so this
+//! applies a simple nearest-neighbor Bayer demosaic rather than a full color
+//! pipeline (white balance, highlight recovery, etc).
+
+use std::path::Path;
+
+/// Extensions recognized as camera RAW files.
+pub fn extensions() -> &'static [&'static str] {
+    &["nef", "cr2", "dng", "arw", "rw2", "raf"]
+}
+
+pub fn is_raw_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| extensions().contains(&ext.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Decodes a RAW file into an RGBA8 buffer along with its dimensions.
+pub fn decode(path: &Path) -> Result<(usize, usize, Vec<u8>), Box<dyn std::error::Error + Send + Sync>> {
+    let raw = rawloader::decode_file(path)?;
+    let width = raw.width;
+    let height = raw.height;
+
+    let rgba = demosaic_to_rgba(&raw);
+
+    Ok((width, height, rgba))
+}
+
+/// Nearest-neighbor Bayer demosaic: for each pixel, sample the two missing
+/// channels from the closest neighbor of the right color rather than
+/// interpolating, which is cheap and good enough for a BlurHash preview.
+fn demosaic_to_rgba(raw: &rawloader::RawImage) -> Vec<u8> {
+    let width = raw.width;
+    let height = raw.height;
+    let data = match &raw.data {
+        rawloader::RawImageData::Integer(d) => d,
+        rawloader::RawImageData::Float(_) => {
+            // Float sensor data is rare; fall back to a black frame rather than panic.
+            return vec![0u8; width * height * 4];
+        }
+    };
+
+    let max = raw.whitelevels[0].max(1) as f32;
+    let mut rgba = vec![0u8; width * height * 4];
+
+    for y in 0..height {
+        for x in 0..width {
+            let color = raw.cfa.color_at(x, y);
+            let sample = |cx: usize, cy: usize| -> u16 { data[cy * width + cx] };
+            let value = sample(x, y) as f32 / max;
+
+            // Take each missing channel from the horizontal, vertical or
+            // diagonal neighbor (in that order) whose CFA color matches it.
+            let nx = if x + 1 < width { x + 1 } else { x.saturating_sub(1) };
+            let ny = if y + 1 < height { y + 1 } else { y.saturating_sub(1) };
+            let neighbor = |want: usize| -> f32 {
+                [(nx, y), (x, ny), (nx, ny)]
+                    .iter()
+                    .find(|&&(cx, cy)| raw.cfa.color_at(cx, cy) == want)
+                    .map_or(value, |&(cx, cy)| sample(cx, cy) as f32 / max)
+            };
+
+            let (r, g, b) = match color {
+                0 => (value, neighbor(1), neighbor(2)),
+                1 => (neighbor(0), value, neighbor(2)),
+                2 => (neighbor(0), neighbor(1), value),
+                _ => (value, value, value),
+            };
+
+            let idx = (y * width + x) * 4;
+            rgba[idx] = (r.clamp(0.0, 1.0) * 255.0) as u8;
+            rgba[idx + 1] = (g.clamp(0.0, 1.0) * 255.0) as u8;
+            rgba[idx + 2] = (b.clamp(0.0, 1.0) * 255.0) as u8;
+            rgba[idx + 3] = 255;
+        }
+    }
+
+    rgba
+}
@@ -0,0 +1,67 @@
+//! Groups images by perceptual similarity using a BK-tree over the
+//! difference-hashes computed from their BlurHashes.
+
+use crate::bktree::{hamming, BkTree};
+use crate::phash::dhash_from_blurhash;
+use std::path::PathBuf;
+
+struct UnionFind {
+    parent: Vec<usize>,
+}
+
+impl UnionFind {
+    fn new(size: usize) -> Self {
+        UnionFind {
+            parent: (0..size).collect(),
+        }
+    }
+
+    fn find(&mut self, x: usize) -> usize {
+        if self.parent[x] != x {
+            self.parent[x] = self.find(self.parent[x]);
+        }
+        self.parent[x]
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (ra, rb) = (self.find(a), self.find(b));
+        if ra != rb {
+            self.parent[ra] = rb;
+        }
+    }
+}
+
+/// Clusters `(path, blurhash)` pairs whose perceptual hashes are within
+/// `radius` Hamming bits of each other, returning each cluster as a list of
+/// paths (singletons are omitted).
+pub fn find_clusters(
+    items: &[(PathBuf, String)],
+    radius: u32,
+) -> Result<Vec<Vec<PathBuf>>, Box<dyn std::error::Error + Send + Sync>> {
+    let hashes: Vec<u64> = items
+        .iter()
+        .map(|(_, hash)| dhash_from_blurhash(hash))
+        .collect::<Result<_, _>>()?;
+
+    let mut tree: BkTree<usize> = BkTree::new();
+    for (index, hash) in hashes.iter().enumerate() {
+        tree.insert(*hash, index);
+    }
+
+    let mut union_find = UnionFind::new(items.len());
+    for (index, hash) in hashes.iter().enumerate() {
+        for &other in tree.find_within(*hash, radius) {
+            if other != index && hamming(*hash, hashes[other]) <= radius {
+                union_find.union(index, other);
+            }
+        }
+    }
+
+    let mut groups: std::collections::HashMap<usize, Vec<PathBuf>> = std::collections::HashMap::new();
+    for index in 0..items.len() {
+        let root = union_find.find(index);
+        groups.entry(root).or_default().push(items[index].0.clone());
+    }
+
+    Ok(groups.into_values().filter(|group| group.len() > 1).collect())
+}
@@ -0,0 +1,93 @@
+//! Video thumbnail extraction, shelling out to `ffmpeg`/`ffprobe` so we don't
+//! need to depend on a particular set of container/codec bindings.
+
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::process::Command;
+
+/// Extensions recognized as video files.
+pub fn extensions() -> &'static [&'static str] {
+    &["mp4", "mkv", "webm", "mov"]
+}
+
+pub fn is_video_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| extensions().contains(&ext.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Extracts a representative frame to a temp PNG file and returns its path.
+///
+/// `frame_at_percent`, if set, picks the timestamp as that percentage of the
+/// video's duration; otherwise a fixed 1 second in is used. Decoding the PNG
+/// itself is left to the caller, which runs it through the same guarded,
+/// `spawn_blocking`-wrapped path as every other image source.
+pub async fn extract_frame_to_file(
+    path: &Path,
+    frame_at_percent: Option<f64>,
+) -> Result<PathBuf, Box<dyn std::error::Error + Send + Sync>> {
+    let timestamp = match frame_at_percent {
+        Some(percent) => probe_duration_secs(path).await? * (percent / 100.0),
+        None => 1.0,
+    };
+
+    let frame_path = std::env::temp_dir().join(format!(
+        "blurhash-frame-{}-{}.png",
+        std::process::id(),
+        uuid_like_suffix(path),
+    ));
+
+    let status = Command::new("ffmpeg")
+        .args(["-y", "-ss"])
+        .arg(format!("{timestamp:.3}"))
+        .arg("-i")
+        .arg(path)
+        .args(["-frames:v", "1"])
+        .arg(&frame_path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await?;
+
+    if !status.success() {
+        return Err(format!("ffmpeg exited with {status}").into());
+    }
+
+    Ok(frame_path)
+}
+
+async fn probe_duration_secs(path: &Path) -> Result<f64, Box<dyn std::error::Error + Send + Sync>> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-show_entries",
+            "format=duration",
+            "-of",
+            "default=noprint_wrappers=1:nokey=1",
+        ])
+        .arg(path)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        return Err(format!("ffprobe exited with {}", output.status).into());
+    }
+
+    let text = String::from_utf8(output.stdout)?;
+    Ok(text.trim().parse::<f64>()?)
+}
+
+/// Cheap per-path suffix so concurrent extractions don't collide on the temp file name.
+fn uuid_like_suffix(path: &Path) -> String {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    format!("{:x}", hasher.finish())
+}